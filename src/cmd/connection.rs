@@ -1,9 +1,13 @@
 use bytes::{Bytes, BytesMut, Buf};
 use tokio::net::TcpStream;
 use mini_redis::{Frame, Result};
+use mini_redis::frame::Error as FrameError;
 use mini_redis::frame::Error::Incomplete;
-use tokio::io::{self, AsyncWriteExt};
-use std::io::Cursor;
+use tokio::io::{self, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter};
+use tokio_util::codec::{Decoder, Encoder};
+use futures::{Stream, StreamExt};
+use std::io::{Cursor, Write};
+use std::marker::PhantomData;
 
 enum Frame {
     Simple(String),
@@ -14,98 +18,110 @@ enum Frame {
     Array(Vec<Frame>),
 }
 
-pub struct Connection {
+/// 一套可插拔的帧协议。
+///
+/// 帧层本身不携带任何命令语义，只负责“从字节流里切出一个帧、再把帧写回字节流”。
+/// 把这部分抽象成 trait 之后，同一套缓冲/游标机制既能驱动 Redis 的 `RespProtocol`，
+/// 也能驱动 HTTP 风格的 `HttpProtocol`，让本 crate 成为一个通用的分帧工具箱，
+/// 而不再只服务于 Redis。
+pub trait Protocol {
+    /// 该协议解析出的帧类型。
+    type Frame;
+
+    /// 检查缓冲区是否已含有一个完整的帧；数据不足时返回 `Incomplete`。
+    fn check(src: &mut Cursor<&[u8]>) -> std::result::Result<(), FrameError>;
+
+    /// 从游标解析出一个帧，调用方已通过 `check` 确认数据充足。
+    fn parse(src: &mut Cursor<&[u8]>) -> Result<Self::Frame>;
+
+    /// 把一个帧编码写入异步写端。
+    async fn encode<W>(frame: &Self::Frame, dst: &mut W) -> Result<()>
+    where
+        W: AsyncWrite + Unpin + Send;
+}
+
+pub struct Connection<P = RespProtocol> {
     stream: BufWriter<TcpStream>,
     buffer: BytesMut,
+    /// 单个帧允许占用的最大字节数，`None` 表示不限制。
+    ///
+    /// 设定上限后，缓冲区不会无限增长：恶意对端发送一个超大的
+    /// bulk 长度时会收到协议错误，而不会把服务端撑爆（OOM）。
+    max_frame_size: Option<usize>,
+    protocol: PhantomData<P>,
 }
 
-impl Connection {
+impl<P: Protocol> Connection<P> {
+
+    pub fn new(stream: TcpStream) -> Connection<P> {
+        Connection {
+            stream: BufWriter::new(stream),
+            buffer: BytesMut::with_capacity(4096),
+            max_frame_size: None,
+            protocol: PhantomData,
+        }
+    }
 
-    pub fn new(stream: TcpStream) -> Connection {
+    /// 创建一个对单帧大小设上限的连接，超过上限即返回协议错误。
+    pub fn with_max_frame_size(stream: TcpStream, max_frame_size: usize) -> Connection<P> {
         Connection {
             stream: BufWriter::new(stream),
             buffer: BytesMut::with_capacity(4096),
+            max_frame_size: Some(max_frame_size),
+            protocol: PhantomData,
         }
     }
 
     pub async fn read_frame(&mut self)
-    -> Result<Option<Frame>>
+    -> Result<Option<P::Frame>>
     {
         loop {
             if let Some(frame) = self.parse_frame()? {
                 return Ok(Some(frame));
             }
-    
-            // 确保缓冲区长度足够
-            if self.buffer.len() == self.cursor {
-                // 若不够，需要增加缓冲区长度
-                self.buffer.resize(self.cursor * 2, 0);
-            }
-    
-            // 从游标位置开始将数据读入缓冲区
-            let n = self.stream.read(
-                &mut self.buffer[self.cursor..]).await?;
-    
-            if 0 == n {
-                if self.cursor == 0 {
+
+            // 缓冲区已攒够上限却仍解析不出完整帧：拒绝继续增长，
+            // 否则一个超大的 bulk 长度就能让内存无限膨胀。
+            if let Some(max) = self.max_frame_size {
+                if self.buffer.len() >= max {
+                    return Err("frame exceeds configured maximum size".into());
+                }
+            }
+
+            // 预留容量而非 resize 到 cursor*2：`parse_frame` 用
+            // `buffer.advance(len)` 消费掉已解析的字节后，`reserve`
+            // 会复用被释放的头部空间，`read_buf` 则把新数据读入
+            // 未初始化的尾部，从而把内存占用限制在活跃帧的大小附近。
+            self.buffer.reserve(4096);
+
+            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+                if self.buffer.is_empty() {
                     return Ok(None);
                 } else {
                     return Err("connection reset by peer".into());
                 }
-            } else {
-                // 更新游标位置
-                self.cursor += n;
             }
         }
     }
 
-    /// 将帧写入到连接中
-    pub async fn write_frame(&mut self, frame: &Frame)
+    /// 将单个帧写入连接并立即 flush。
+    pub async fn write_frame(&mut self, frame: &P::Frame)
         -> Result<()>
         {
-            match frame {
-                Frame::Simple(val) => {
-                    self.stream.write_u8(b'+').await?;
-                    self.stream.write_all(val.as_bytes()).await?;
-                    self.stream.write_all(b"\r\n").await?;
-                }
-                Frame::Error(val) => {
-                    self.stream.write_u8(b'-').await?;
-                    self.stream.write_all(val.as_bytes()).await?;
-                    self.stream.write_all(b"\r\n").await?;
-                }
-                Frame::Integer(val) => {
-                    self.stream.write_u8(b':').await?;
-                    self.write_decimal(*val).await?;
-                }
-                Frame::Null => {
-                    self.stream.write_all(b"$-1\r\n").await?;
-                }
-                Frame::Bulk(val) => {
-                    let len = val.len();
-        
-                    self.stream.write_u8(b'$').await?;
-                    self.write_decimal(len as u64).await?;
-                    self.stream.write_all(val).await?;
-                    self.stream.write_all(b"\r\n").await?;
-                }
-                Frame::Array(_val) => unimplemented!(),
-            }
-        
-            self.stream.flush().await;
-        
+            P::encode(frame, &mut self.stream).await?;
+            self.stream.flush().await?;
             Ok(())
         }
 
     // 帧解析
     fn parse_frame(&mut self)
-    -> Result<Option<Frame>>
+    -> Result<Option<P::Frame>>
     {
         // 创建 `T: Buf` 类型
         let mut buf = Cursor::new(&self.buffer[..]);
 
         // 检查是否读取了足够解析出一个帧的数据
-        match Frame::check(&mut buf) {
+        match P::check(&mut buf) {
             Ok(_) => {
                 // 获取组成该帧的字节数
                 let len = buf.position() as usize;
@@ -114,7 +130,14 @@ impl Connection {
                 buf.set_position(0);
 
                 // 解析帧
-                let frame = Frame::parse(&mut buf)?;
+                let frame = P::parse(&mut buf)?;
+
+                // 一次读入就已完整的帧同样受单帧上限约束
+                if let Some(max) = self.max_frame_size {
+                    if len > max {
+                        return Err("frame exceeds configured maximum size".into());
+                    }
+                }
 
                 // 解析完成，将缓冲区该帧的数据移除
                 self.buffer.advance(len);
@@ -128,4 +151,373 @@ impl Connection {
             Err(e) => Err(e.into()),
         }
     }
-}
\ No newline at end of file
+}
+
+/// RESP 特有的扩展：批量写入与大 bulk 的流式读写。
+///
+/// 这些方法依赖 `Frame` 的具体结构（数组、bulk 长度前缀等），因此只在
+/// `RespProtocol` 上提供，而不放进通用的 `Protocol` 抽象里。
+impl Connection<RespProtocol> {
+    /// 批量写入多个帧，最后只 flush 一次。
+    ///
+    /// 流水线客户端一次性发来多条命令时，逐帧 flush 会为每个回复触发
+    /// 一次系统调用、严重拖慢吞吐；此方法把所有回复先写入内部
+    /// `BufWriter`，再统一刷新。
+    pub async fn write_frames(&mut self, frames: &[Frame])
+        -> Result<()>
+        {
+            for frame in frames {
+                RespProtocol::encode(frame, &mut self.stream).await?;
+            }
+            self.stream.flush().await?;
+            Ok(())
+        }
+
+    /// 以分块的 `Stream<Bytes>` 形式读取一个 bulk 负载。
+    ///
+    /// 假定 `$<len>\r\n` 头已经解析、`len` 已知。负载并不会整段堆进
+    /// `buffer`（这正是忽略 `Content-Length` 的朴素 TCP 服务端会踩的坑），
+    /// 而是先吐出缓冲区里已有的字节，再按 `read_exact` 的思路直接从套接字
+    /// 拉取剩余的 `len - buffered` 字节，最后消费结尾的 `\r\n`。适合以
+    /// 受控内存代理或落盘超大值。
+    pub fn read_bulk_stream(&mut self, len: usize)
+        -> impl Stream<Item = Result<Bytes>> + '_
+    {
+        async_stream::try_stream! {
+            let mut remaining = len;
+
+            // 先把缓冲区里已经收到的那部分负载吐出去
+            while remaining > 0 && !self.buffer.is_empty() {
+                let take = std::cmp::min(remaining, self.buffer.len());
+                let chunk = self.buffer.split_to(take).freeze();
+                remaining -= chunk.len();
+                yield chunk;
+            }
+
+            // 其余字节直接从套接字按块读取，不再整段缓冲
+            while remaining > 0 {
+                self.buffer.reserve(4096);
+                let n = self.stream.read_buf(&mut self.buffer).await?;
+                if n == 0 {
+                    Err::<(), mini_redis::Error>("connection reset by peer".into())?;
+                }
+                let take = std::cmp::min(remaining, self.buffer.len());
+                let chunk = self.buffer.split_to(take).freeze();
+                remaining -= chunk.len();
+                yield chunk;
+            }
+
+            // 消费 bulk 负载结尾的 \r\n
+            self.read_crlf().await?;
+        }
+    }
+
+    /// 以分块流的形式写出一个已知长度的 bulk 负载。
+    ///
+    /// 先写出 `$<len>\r\n` 头，再逐块转发 `stream` 的内容，最后补上
+    /// 结尾的 `\r\n` 并 flush，是 `read_bulk_stream` 的写出对端。
+    pub async fn write_bulk_stream<S>(&mut self, len: usize, mut stream: S)
+        -> Result<()>
+    where
+        S: Stream<Item = Result<Bytes>> + Unpin,
+    {
+        self.stream.write_u8(b'$').await?;
+        self.write_decimal(len as u64).await?;
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            self.stream.write_all(&chunk).await?;
+        }
+
+        self.stream.write_all(b"\r\n").await?;
+        self.stream.flush().await?;
+
+        Ok(())
+    }
+
+    /// 将一个十进制整数连同结尾的 `\r\n` 写入流。
+    async fn write_decimal(&mut self, val: u64) -> io::Result<()> {
+        let mut buf = [0u8; 20];
+        let mut cur = Cursor::new(&mut buf[..]);
+        write!(&mut cur, "{}", val)?;
+
+        let pos = cur.position() as usize;
+        self.stream.write_all(&buf[..pos]).await?;
+        self.stream.write_all(b"\r\n").await?;
+
+        Ok(())
+    }
+
+    /// 确保缓冲区至少含有结尾的 `\r\n` 并将其消费掉。
+    async fn read_crlf(&mut self) -> Result<()> {
+        while self.buffer.len() < 2 {
+            self.buffer.reserve(2);
+            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+                return Err("connection reset by peer".into());
+            }
+        }
+
+        if &self.buffer[..2] != b"\r\n" {
+            return Err("protocol error; expected CRLF after bulk payload".into());
+        }
+
+        self.buffer.advance(2);
+        Ok(())
+    }
+}
+
+/// Redis 序列化协议（RESP）。
+///
+/// 把原先内联在 `Connection` 里的 `check`/`parse`/`encode` 逻辑收拢到这里，
+/// 作为默认协议实现。
+#[derive(Debug, Default)]
+pub struct RespProtocol;
+
+impl Protocol for RespProtocol {
+    type Frame = Frame;
+
+    fn check(src: &mut Cursor<&[u8]>) -> std::result::Result<(), FrameError> {
+        Frame::check(src)
+    }
+
+    fn parse(src: &mut Cursor<&[u8]>) -> Result<Frame> {
+        Ok(Frame::parse(src)?)
+    }
+
+    async fn encode<W>(frame: &Frame, dst: &mut W) -> Result<()>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        // 复用同步的 `encode_frame` 递归编码（含嵌套数组、null），
+        // 再一次性写入异步写端。
+        let mut buf = BytesMut::new();
+        encode_frame(frame, &mut buf);
+        dst.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+/// HTTP 风格的帧，用来佐证帧层与命令语义无关、可承载第二种协议。
+pub enum HttpFrame {
+    RequestHead {
+        method: String,
+        uri: String,
+        headers: Vec<(String, String)>,
+    },
+    ResponseHead {
+        status: u16,
+        headers: Vec<(String, String)>,
+    },
+    BodyChunk {
+        data: Bytes,
+    },
+}
+
+/// HTTP 风格协议：请求行/状态行 + 头部。
+///
+/// 与 `RespProtocol` 共用同一套 `Connection` 缓冲机制。`parse` 只解析到
+/// 头部结束标记 `\r\n\r\n` 为止，产出 `RequestHead` 或 `ResponseHead`，
+/// 不产出 `BodyChunk`，也不读取 body；`encode` 把 `BodyChunk` 的数据原样写出。
+/// `read_bulk_stream`/`write_bulk_stream` 只在 `Connection<RespProtocol>` 上提供。
+#[derive(Debug, Default)]
+pub struct HttpProtocol;
+
+impl HttpProtocol {
+    /// 在缓冲区里定位 `\r\n\r\n`（头部结束标记）的下一个位置。
+    fn head_end(src: &[u8]) -> Option<usize> {
+        src.windows(4).position(|w| w == b"\r\n\r\n").map(|p| p + 4)
+    }
+}
+
+impl Protocol for HttpProtocol {
+    type Frame = HttpFrame;
+
+    fn check(src: &mut Cursor<&[u8]>) -> std::result::Result<(), FrameError> {
+        match HttpProtocol::head_end(src.get_ref()) {
+            Some(end) => {
+                src.set_position(end as u64);
+                Ok(())
+            }
+            // 头部尚未接收完整，继续读取
+            None => Err(Incomplete),
+        }
+    }
+
+    fn parse(src: &mut Cursor<&[u8]>) -> Result<HttpFrame> {
+        let end = HttpProtocol::head_end(src.get_ref())
+            .ok_or("protocol error; incomplete HTTP head")?;
+        let head = std::str::from_utf8(&src.get_ref()[..end])?;
+
+        let mut lines = head.split("\r\n");
+        let start_line = lines.next().unwrap_or("");
+
+        let mut headers = Vec::new();
+        for line in lines {
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+
+        let frame = if let Some(rest) = start_line.strip_prefix("HTTP/") {
+            // 状态行形如 `HTTP/1.1 200 OK`
+            let status = rest
+                .split_whitespace()
+                .nth(1)
+                .and_then(|code| code.parse().ok())
+                .ok_or("protocol error; invalid status line")?;
+            HttpFrame::ResponseHead { status, headers }
+        } else {
+            // 请求行形如 `GET /path HTTP/1.1`
+            let mut parts = start_line.split_whitespace();
+            let method = parts.next().ok_or("protocol error; invalid request line")?.to_string();
+            let uri = parts.next().ok_or("protocol error; invalid request line")?.to_string();
+            HttpFrame::RequestHead { method, uri, headers }
+        };
+
+        Ok(frame)
+    }
+
+    async fn encode<W>(frame: &HttpFrame, dst: &mut W) -> Result<()>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let mut buf = BytesMut::new();
+        match frame {
+            HttpFrame::RequestHead { method, uri, headers } => {
+                buf.extend_from_slice(method.as_bytes());
+                buf.extend_from_slice(b" ");
+                buf.extend_from_slice(uri.as_bytes());
+                buf.extend_from_slice(b" HTTP/1.1\r\n");
+                encode_http_headers(&mut buf, headers);
+                buf.extend_from_slice(b"\r\n");
+            }
+            HttpFrame::ResponseHead { status, headers } => {
+                buf.extend_from_slice(b"HTTP/1.1 ");
+                write_decimal_buf_inline(&mut buf, *status as u64);
+                buf.extend_from_slice(b"\r\n");
+                encode_http_headers(&mut buf, headers);
+                buf.extend_from_slice(b"\r\n");
+            }
+            HttpFrame::BodyChunk { data } => {
+                buf.extend_from_slice(data);
+            }
+        }
+        dst.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+/// 把一组 HTTP 头部写入缓冲区。
+fn encode_http_headers(dst: &mut BytesMut, headers: &[(String, String)]) {
+    for (name, value) in headers {
+        dst.extend_from_slice(name.as_bytes());
+        dst.extend_from_slice(b": ");
+        dst.extend_from_slice(value.as_bytes());
+        dst.extend_from_slice(b"\r\n");
+    }
+}
+
+/// 基于 `tokio_util::codec` 的 RESP 编解码器。
+///
+/// 把 `RedisCodec` 与 `Framed` 组合，就能将任意 `AsyncRead + AsyncWrite`
+/// 包装成 `Stream<Item = Result<Frame>>` 和 `Sink<Frame>`，从而复用
+/// `select!`、`StreamExt`、背压与超时等组合子，而无需手写
+/// `read_frame`/`write_frame` 里的缓冲逻辑。
+#[derive(Debug, Default)]
+pub struct RedisCodec;
+
+impl Decoder for RedisCodec {
+    type Item = Frame;
+    type Error = mini_redis::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Frame>> {
+        // 与 parse_frame 相同的流程：先在只读游标上 check，再 parse
+        let mut buf = Cursor::new(&src[..]);
+
+        match Frame::check(&mut buf) {
+            Ok(_) => {
+                // 记录组成该帧的字节数
+                let len = buf.position() as usize;
+
+                // 重置游标后再解析
+                buf.set_position(0);
+                let frame = Frame::parse(&mut buf)?;
+
+                // 从缓冲区移除已消费的字节
+                src.advance(len);
+
+                Ok(Some(frame))
+            }
+            // 数据还不够，交还给框架继续读取
+            Err(Incomplete) => Ok(None),
+            // 其它错误映射为编解码错误
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Encoder<Frame> for RedisCodec {
+    type Error = mini_redis::Error;
+
+    fn encode(&mut self, frame: Frame, dst: &mut BytesMut) -> Result<()> {
+        // 复用 encode_frame 的递归编码，直接写入 dst
+        encode_frame(&frame, dst);
+        Ok(())
+    }
+}
+
+/// 递归地把一个帧编码到缓冲区，与 `RespProtocol::encode` 对应。
+fn encode_frame(frame: &Frame, dst: &mut BytesMut) {
+    match frame {
+        Frame::Simple(val) => {
+            dst.extend_from_slice(b"+");
+            dst.extend_from_slice(val.as_bytes());
+            dst.extend_from_slice(b"\r\n");
+        }
+        Frame::Error(val) => {
+            dst.extend_from_slice(b"-");
+            dst.extend_from_slice(val.as_bytes());
+            dst.extend_from_slice(b"\r\n");
+        }
+        Frame::Integer(val) => {
+            dst.extend_from_slice(b":");
+            write_decimal_buf(dst, *val);
+        }
+        Frame::Null => {
+            dst.extend_from_slice(b"$-1\r\n");
+        }
+        Frame::Bulk(val) => {
+            dst.extend_from_slice(b"$");
+            write_decimal_buf(dst, val.len() as u64);
+            dst.extend_from_slice(val);
+            dst.extend_from_slice(b"\r\n");
+        }
+        Frame::Array(val) => {
+            dst.extend_from_slice(b"*");
+            write_decimal_buf(dst, val.len() as u64);
+            for entry in val {
+                encode_frame(entry, dst);
+            }
+        }
+    }
+}
+
+/// 将一个十进制整数连同结尾的 `\r\n` 写入缓冲区。
+fn write_decimal_buf(dst: &mut BytesMut, val: u64) {
+    write_decimal_buf_inline(dst, val);
+    dst.extend_from_slice(b"\r\n");
+}
+
+/// 仅写入十进制整数本身，不附带分隔符。
+fn write_decimal_buf_inline(dst: &mut BytesMut, val: u64) {
+    let mut buf = [0u8; 20];
+    let mut cur = Cursor::new(&mut buf[..]);
+    // 写入栈上小缓冲区不会失败
+    write!(&mut cur, "{}", val).unwrap();
+
+    let pos = cur.position() as usize;
+    dst.extend_from_slice(&buf[..pos]);
+}